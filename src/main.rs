@@ -1,7 +1,21 @@
-//extern crate crypto;
+extern crate blake3;
+extern crate byteorder;
+extern crate flate2;
 extern crate getopts;
+extern crate libc;
+extern crate memmap2;
+extern crate rand;
+extern crate sha2;
 
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use getopts::Options;
+use memmap2::{Mmap, MmapMut};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use sha2::{Digest, Sha256};
 use std::cmp;
 use std::env;
 use std::fs::File;
@@ -10,6 +24,71 @@ use std::io;
 use std::io::prelude::*;
 use std::io::{Seek, SeekFrom};
 use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Patch container format: an 8-byte magic (non-ASCII first byte plus a
+// trailing CR-LF, following the PNG/mbon convention, to catch transfers
+// mangled by a text-mode copy), a one-byte format version, then a plain
+// (uncompressed) header, followed by a zlib-compressed stream of records.
+const PATCH_MAGIC: [u8; 8] = [0x8f, b'D', b'S', b'Y', b'N', b'C', b'\r', b'\n'];
+const PATCH_VERSION: u8 = 1;
+
+// Manifest container format: same magic/version convention as patch files,
+// but followed by the hash algorithm, block size, then one fixed-size hash
+// per source block, in order.
+const MANIFEST_MAGIC: [u8; 8] = [0x8f, b'M', b'A', b'N', b'I', b'F', b'\r', b'\n'];
+const MANIFEST_VERSION: u8 = 1;
+const HASH_LEN: usize = 32;
+
+// Block hash algorithm used by --manifest-out/--manifest-in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HashAlgo {
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgo {
+    fn parse(name: &str) -> HashAlgo {
+        match name {
+            "sha256" => HashAlgo::Sha256,
+            "blake3" => HashAlgo::Blake3,
+            _ => panic!("Invalid --hash algorithm ({})", name),
+        }
+    }
+
+    fn to_byte(&self) -> u8 {
+        match self {
+            HashAlgo::Sha256 => 0,
+            HashAlgo::Blake3 => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> HashAlgo {
+        match byte {
+            0 => HashAlgo::Sha256,
+            1 => HashAlgo::Blake3,
+            _ => panic!("Invalid hash algorithm byte in manifest ({})", byte),
+        }
+    }
+
+    fn hash(&self, data: &[u8]) -> [u8; HASH_LEN] {
+        match self {
+            HashAlgo::Sha256 => {
+                let mut hasher = Sha256::new();
+                hasher.update(data);
+                let digest = hasher.finalize();
+                let mut out = [0u8; HASH_LEN];
+                out.copy_from_slice(&digest);
+                out
+            }
+            HashAlgo::Blake3 => *blake3::hash(data).as_bytes(),
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Config {
@@ -18,6 +97,152 @@ struct Config {
     block_size: usize,
     buf_size: usize,
     dry_run: bool,
+    jobs: usize,
+    status: StatusLevel,
+    patch: Option<String>,
+    apply: Option<String>,
+    mmap: bool,
+    manifest_out: Option<String>,
+    manifest_in: Option<String>,
+    hash: HashAlgo,
+    extend: bool,
+    truncate: bool,
+}
+
+// How much the background status thread prints while a sync is in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatusLevel {
+    None,
+    NoXfer,
+    Progress,
+}
+
+impl StatusLevel {
+    fn parse(level: &str) -> StatusLevel {
+        match level {
+            "none" => StatusLevel::None,
+            "noxfer" => StatusLevel::NoXfer,
+            "progress" => StatusLevel::Progress,
+            _ => panic!("Invalid --status level ({})", level),
+        }
+    }
+}
+
+// Counters shared between the comparison loop(s) and the status thread, so
+// progress can be read without disturbing the loop. A SIGUSR1 sent to this
+// process sets `USR1_RECEIVED`, asking the status thread for an immediate
+// one-shot dump without interrupting the sync itself.
+struct LiveStats {
+    blocks_compared: AtomicUsize,
+    diff_blocks: AtomicUsize,
+    diff_bytes: AtomicUsize,
+    total_bytes: AtomicUsize,
+}
+
+impl LiveStats {
+    fn new() -> LiveStats {
+        LiveStats {
+            blocks_compared: AtomicUsize::new(0),
+            diff_blocks: AtomicUsize::new(0),
+            diff_bytes: AtomicUsize::new(0),
+            total_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    fn snapshot(&self) -> SyncStats {
+        SyncStats {
+            blocks_compared: self.blocks_compared.load(Ordering::Relaxed),
+            diff_blocks: self.diff_blocks.load(Ordering::Relaxed),
+            diff_bytes: self.diff_bytes.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes.load(Ordering::Relaxed),
+            ..SyncStats::default()
+        }
+    }
+}
+
+// Set by the SIGUSR1 handler; polled (not acted on) by the status thread,
+// since printing from inside a signal handler is not safe.
+static USR1_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_usr1(_signum: libc::c_int) {
+    USR1_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+fn install_usr1_handler() {
+    unsafe {
+        libc::signal(libc::SIGUSR1, handle_usr1 as *const () as libc::sighandler_t);
+    }
+}
+
+// Print one line of transfer statistics to stderr, refreshing in place
+// (no newline) unless this is a one-shot SIGUSR1 dump.
+fn print_live_stats(status: StatusLevel, stats: &SyncStats, elapsed: Duration, one_shot: bool) {
+    let secs = elapsed.as_secs_f64().max(0.001);
+    let mut line = format!(
+        "{} blocks compared, {} differing, {} written, {:.1}s elapsed",
+        stats.blocks_compared,
+        stats.diff_blocks,
+        size_to_str(&stats.diff_bytes),
+        secs
+    );
+    if status == StatusLevel::Progress {
+        let rate = stats.total_bytes as f64 / secs;
+        line.push_str(&format!(", {}/s", size_to_str(&(rate as usize))));
+    }
+    if one_shot {
+        eprintln!("{}", line);
+    } else {
+        eprint!("\r{}\x1b[K", line);
+        let _ = io::stderr().flush();
+    }
+}
+
+// Background thread that, while a sync runs, periodically prints progress
+// (when `status` asks for it) and reacts to SIGUSR1 by printing an immediate
+// one-shot dump of the same statistics.
+fn run_status_thread(status: StatusLevel, live: Arc<LiveStats>, stop: Arc<AtomicBool>) {
+    let start = Instant::now();
+    let tick = Duration::from_millis(200);
+    let mut since_last_print = Duration::from_secs(0);
+    loop {
+        thread::sleep(tick);
+        since_last_print += tick;
+
+        if USR1_RECEIVED.swap(false, Ordering::SeqCst) {
+            print_live_stats(status, &live.snapshot(), start.elapsed(), true);
+        }
+
+        if status != StatusLevel::None && since_last_print >= Duration::from_secs(1) {
+            print_live_stats(status, &live.snapshot(), start.elapsed(), false);
+            since_last_print = Duration::from_secs(0);
+        }
+
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+    if status != StatusLevel::None {
+        eprintln!();
+    }
+}
+
+// Stats gathered while comparing (and optionally syncing) a file, either by
+// the single-threaded path or summed up from all workers of the parallel one.
+#[derive(Debug, Default, Clone, Copy)]
+struct SyncStats {
+    blocks_compared: usize,
+    diff_blocks: usize,
+    diff_bytes: usize,
+    total_bytes: usize,
+    extended_bytes: usize,
+    truncated_bytes: usize,
+}
+
+// A disjoint, half-open range of blocks assigned to a single worker thread.
+#[derive(Debug, Clone, Copy)]
+struct Chunk {
+    start_block: usize,
+    end_block: usize,
 }
 
 fn print_usage(program: &str, opts: Options) {
@@ -64,6 +289,63 @@ fn parse_opts() -> Config {
         "source of synchronization (default is stdin)",
         "FILE",
     );
+    opts.optopt(
+        "j",
+        "jobs",
+        "number of worker threads to compare blocks with (default 1, requires a seekable --source)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "status",
+        "progress reporting level: none, noxfer, progress (default none)",
+        "LEVEL",
+    );
+    opts.optopt(
+        "",
+        "patch",
+        "write differing blocks to FILE as a compressed patch instead of (or in addition to) syncing directly",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "apply",
+        "apply a patch written with --patch to the destination",
+        "FILE",
+    );
+    opts.optflag(
+        "",
+        "mmap",
+        "memory-map source and destination and compare directly over the mapping (requires a seekable --source, falls back otherwise)",
+    );
+    opts.optopt(
+        "",
+        "manifest-out",
+        "while scanning the source, write a block-hash manifest to FILE",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "manifest-in",
+        "verify destination blocks against a manifest written with --manifest-out, only syncing blocks whose hash differs",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "hash",
+        "hash algorithm for manifests: sha256, blake3 (default blake3)",
+        "ALGO",
+    );
+    opts.optflag(
+        "",
+        "extend",
+        "if the source is longer than the destination, append the remaining source blocks",
+    );
+    opts.optflag(
+        "",
+        "truncate",
+        "if the destination is longer than the source, shrink it to the source length",
+    );
     opts.optflag("d", "dry-run", "compare but do not write");
     opts.optflag("h", "help", "print this help menu");
     let matches = match opts.parse(&args[1..]) {
@@ -86,6 +368,28 @@ fn parse_opts() -> Config {
         1
     };
     let dry_run = matches.opt_present("d");
+    let jobs: usize = if let Some(ref val) = matches.opt_str("j") {
+        val.parse().expect("jobs")
+    } else {
+        1
+    };
+    let status = if let Some(ref val) = matches.opt_str("status") {
+        StatusLevel::parse(val)
+    } else {
+        StatusLevel::None
+    };
+    let patch = matches.opt_str("patch");
+    let apply = matches.opt_str("apply");
+    let mmap = matches.opt_present("mmap");
+    let manifest_out = matches.opt_str("manifest-out");
+    let manifest_in = matches.opt_str("manifest-in");
+    let hash = if let Some(ref val) = matches.opt_str("hash") {
+        HashAlgo::parse(val)
+    } else {
+        HashAlgo::Blake3
+    };
+    let extend = matches.opt_present("extend");
+    let truncate = matches.opt_present("truncate");
     let dest = if matches.free.len() == 1 {
         matches.free[0].clone()
     } else {
@@ -99,6 +403,16 @@ fn parse_opts() -> Config {
         dry_run: dry_run,
         block_size: block_size,
         buf_size: buf_blocks * block_size,
+        jobs: jobs,
+        status: status,
+        patch: patch,
+        apply: apply,
+        mmap: mmap,
+        manifest_out: manifest_out,
+        manifest_in: manifest_in,
+        hash: hash,
+        extend: extend,
+        truncate: truncate,
     }
 }
 
@@ -131,7 +445,13 @@ where
 }
 
 // Compare and sync two files
-fn sync_files<T>(config: &Config, src_file: &mut T, dest_file: &mut File)
+fn sync_files<T>(
+    config: &Config,
+    src_file: &mut T,
+    dest_file: &mut File,
+    live: &LiveStats,
+    mut patch: Option<&mut PatchWriter>,
+) -> SyncStats
 where
     T: Read,
 {
@@ -139,6 +459,8 @@ where
     let mut diff_bytes = 0;
     let mut total_bytes = 0;
     let mut block_cnt = 0;
+    let mut extended_bytes = 0;
+    let mut truncated_bytes = 0;
 
     // Pre-allocate the buffers to use
     let mut src_buf: Vec<u8> = vec![0; config.buf_size as usize];
@@ -148,8 +470,16 @@ where
         // Fill both buffers to be used for comparsion.
         let src_buf_size = fill_buf(src_file, &mut src_buf);
         let dest_buf_size = fill_buf(dest_file, &mut dest_buf);
-        if src_buf_size == 0 || dest_buf_size == 0 {
-            // Reached eof for one of the files
+        if src_buf_size == 0 {
+            // Source is exhausted. If the destination still has trailing
+            // data, the lengths differ; --truncate decides whether that's
+            // cut off rather than just leaving it in place.
+            if !config.dry_run && config.truncate {
+                let dest_len = dest_file.metadata().expect("stat destination").len();
+                if dest_len > total_bytes as u64 {
+                    truncated_bytes = truncate_destination(dest_file, total_bytes);
+                }
+            }
             break;
         }
 
@@ -170,6 +500,12 @@ where
             if src_slice != dest_slice {
                 diff_blocks += 1;
                 diff_bytes += cmp_size;
+                live.diff_blocks.fetch_add(1, Ordering::Relaxed);
+                live.diff_bytes.fetch_add(cmp_size, Ordering::Relaxed);
+
+                if let Some(ref mut patch) = patch {
+                    patch.write_record(block_cnt as u64, src_slice);
+                }
 
                 if !config.dry_run {
                     // Store current position of dest file
@@ -196,29 +532,708 @@ where
             block_cnt += 1;
             buf_offs += cmp_size;
             total_bytes += cmp_size;
+            live.blocks_compared.fetch_add(1, Ordering::Relaxed);
+            live.total_bytes.fetch_add(cmp_size, Ordering::Relaxed);
+        }
+
+        if dest_buf_size < src_buf_size {
+            // The destination ran out somewhere inside (or at the very start
+            // of) this buffer; `fill_buf` only returns a short read on EOF,
+            // so this is a real length mismatch, not a transient short read.
+            // Anything from `buf_offs` onward was read from the source but
+            // never compared, so it must be captured here before the next
+            // `fill_buf` call overwrites it with further-along data.
+            if !config.dry_run && config.extend {
+                extended_bytes = extend_destination(
+                    config,
+                    dest_file,
+                    total_bytes,
+                    &src_buf[buf_offs..src_buf_size],
+                    src_file,
+                );
+            }
+            break;
+        }
+    }
+
+    SyncStats {
+        blocks_compared: block_cnt,
+        diff_blocks: diff_blocks,
+        diff_bytes: diff_bytes,
+        total_bytes: total_bytes,
+        extended_bytes: extended_bytes,
+        truncated_bytes: truncated_bytes,
+    }
+}
+
+// Append `leftover` (the partial buffer already read past the point where
+// the destination ran out) to the destination, then keep streaming the rest
+// of `src_file` straight onto the end, growing the destination to match.
+fn extend_destination<T>(
+    config: &Config,
+    dest_file: &mut File,
+    dest_len: usize,
+    leftover: &[u8],
+    src_file: &mut T,
+) -> usize
+where
+    T: Read,
+{
+    dest_file
+        .seek(SeekFrom::Start(dest_len as u64))
+        .expect("seek dest end");
+    dest_file.write_all(leftover).expect("write leftover");
+    let mut appended = leftover.len();
+
+    let mut buf: Vec<u8> = vec![0; config.buf_size];
+    loop {
+        let read = fill_buf(src_file, &mut buf);
+        if read == 0 {
+            break;
+        }
+        dest_file.write_all(&buf[..read]).expect("write extend");
+        appended += read;
+    }
+
+    appended
+}
+
+// Shrink the destination down to the length already synced (the source's
+// length), dropping whatever trailed past the end of the source.
+fn truncate_destination(dest_file: &mut File, src_len: usize) -> usize {
+    let old_len = dest_file.metadata().expect("stat destination").len();
+    let new_len = src_len as u64;
+    dest_file.set_len(new_len).expect("truncate destination");
+    (old_len - new_len) as usize
+}
+
+// Shared --extend/--truncate fixup for the --jobs, --mmap, and --manifest-in
+// paths: unlike the buffered `sync_files` path, which discovers a length
+// mismatch inline as part of its own EOF handling, these compare the
+// destination in block-sized pieces and only learn the two lengths differ
+// once that pass is over. Reuses `extend_destination`/`truncate_destination`
+// rather than duplicating the length-mismatch handling in each path.
+fn finalize_length_mismatch(config: &Config, dest_file: &mut File) -> (usize, usize) {
+    if config.dry_run || !(config.extend || config.truncate) {
+        return (0, 0);
+    }
+    let src_path = match &config.src {
+        Some(path) => path,
+        None => {
+            eprintln!("--extend/--truncate require a seekable --source; destination length left unchanged.");
+            return (0, 0);
         }
+    };
+    let dest_len = dest_file.metadata().expect("stat destination").len() as usize;
+    let src_len = std::fs::metadata(src_path).expect("stat source").len() as usize;
+
+    if config.truncate && dest_len > src_len {
+        (0, truncate_destination(dest_file, src_len))
+    } else if config.extend && src_len > dest_len {
+        let mut src_file = File::open(src_path).expect(&format!("failed to open file ({})", src_path));
+        src_file
+            .seek(SeekFrom::Start(dest_len as u64))
+            .expect("seek src");
+        (extend_destination(config, dest_file, dest_len, &[], &mut src_file), 0)
+    } else {
+        (0, 0)
     }
+}
 
+// Compare (and, unless dry-run, sync) two files by memory-mapping both and
+// diffing block-sized slices directly, avoiding the buffer allocation, EOF
+// refill loop, and per-diff seek/write/seek-restore dance of `sync_files`.
+fn sync_files_mmap(config: &Config, src_path: &str, dest_file: &mut File, live: &LiveStats) -> SyncStats {
+    let src_file = File::open(src_path).expect(&format!("failed to open file ({})", src_path));
+    let src_map = unsafe { Mmap::map(&src_file).expect("mmap source") };
+
+    let mut stats = SyncStats::default();
+    let block_size = config.block_size;
+
+    // Iterate by byte offset rather than a floor-divided block count, so a
+    // trailing partial block (common whenever the file length isn't a
+    // multiple of `block_size`) is still compared and synced, matching
+    // `sync_files`.
+    if config.dry_run {
+        let dest_map = unsafe { Mmap::map(&*dest_file).expect("mmap destination") };
+        let cmp_len = cmp::min(src_map.len(), dest_map.len());
+        for start in (0..cmp_len).step_by(block_size) {
+            let end = cmp::min(start + block_size, cmp_len);
+            compare_mapped_block(&src_map[start..end], &dest_map[start..end], &mut stats, live);
+        }
+    } else {
+        let mut dest_map = unsafe { MmapMut::map_mut(&*dest_file).expect("mmap destination") };
+        let cmp_len = cmp::min(src_map.len(), dest_map.len());
+        for start in (0..cmp_len).step_by(block_size) {
+            let end = cmp::min(start + block_size, cmp_len);
+            let differs = {
+                let src_slice = &src_map[start..end];
+                let dest_slice = &dest_map[start..end];
+                compare_mapped_block(src_slice, dest_slice, &mut stats, live);
+                src_slice != dest_slice
+            };
+            if differs {
+                dest_map[start..end].copy_from_slice(&src_map[start..end]);
+            }
+        }
+        dest_map.flush().expect("flush mmap");
+    }
+
+    stats
+}
+
+// Compare one block-sized pair of mapped slices, updating both the local
+// and live (shared) counters. Writing back the block, if it differs, is
+// left to the caller since a read-only `Mmap` can't be written to.
+fn compare_mapped_block(src_slice: &[u8], dest_slice: &[u8], stats: &mut SyncStats, live: &LiveStats) {
+    let block_size = src_slice.len();
+    if src_slice != dest_slice {
+        stats.diff_blocks += 1;
+        stats.diff_bytes += block_size;
+        live.diff_blocks.fetch_add(1, Ordering::Relaxed);
+        live.diff_bytes.fetch_add(block_size, Ordering::Relaxed);
+    }
+    stats.blocks_compared += 1;
+    stats.total_bytes += block_size;
+    live.blocks_compared.fetch_add(1, Ordering::Relaxed);
+    live.total_bytes.fetch_add(block_size, Ordering::Relaxed);
+}
+
+fn print_summary(config: &Config, stats: &SyncStats) {
     println!(
         "Compared {} blocks ({} in total).",
-        block_cnt,
-        size_to_str(&total_bytes)
+        stats.blocks_compared,
+        size_to_str(&stats.total_bytes)
     );
     println!(
         "{} blocks differed ({} in total) {} written to destination.",
-        diff_blocks,
-        size_to_str(&diff_bytes),
+        stats.diff_blocks,
+        size_to_str(&stats.diff_bytes),
         if config.dry_run {
             "but was NOT"
         } else {
             "and was"
         }
     );
+    if stats.extended_bytes > 0 {
+        println!(
+            "destination extended by {}",
+            size_to_str(&stats.extended_bytes)
+        );
+    }
+    if stats.truncated_bytes > 0 {
+        println!(
+            "destination truncated by {}",
+            size_to_str(&stats.truncated_bytes)
+        );
+    }
+}
+
+// Simple fold/xor checksum over a payload; cheap enough to compute per
+// record without pulling in a dedicated CRC32 crate.
+fn checksum(data: &[u8]) -> u32 {
+    let mut sum: u32 = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        sum ^= (byte as u32).wrapping_shl((i as u32 % 4) * 8);
+    }
+    sum
+}
+
+// Writes a patch file: the plain magic/version/header, followed by a
+// zlib-compressed stream of (block_index, payload_len, checksum, payload)
+// records, one per differing block.
+struct PatchWriter {
+    encoder: ZlibEncoder<File>,
+}
+
+impl PatchWriter {
+    fn create(path: &str, block_size: usize, src_len: u64, dest_len: u64) -> PatchWriter {
+        let mut file = File::create(path).expect(&format!("failed to create patch file ({})", path));
+        file.write_all(&PATCH_MAGIC).expect("write magic");
+        file.write_u8(PATCH_VERSION).expect("write version");
+        file.write_u64::<LittleEndian>(block_size as u64)
+            .expect("write block_size");
+        file.write_u64::<LittleEndian>(src_len).expect("write src_len");
+        file.write_u64::<LittleEndian>(dest_len)
+            .expect("write dest_len");
+
+        PatchWriter {
+            encoder: ZlibEncoder::new(file, Compression::default()),
+        }
+    }
+
+    fn write_record(&mut self, block_index: u64, payload: &[u8]) {
+        self.encoder
+            .write_u64::<LittleEndian>(block_index)
+            .expect("write block index");
+        self.encoder
+            .write_u32::<LittleEndian>(payload.len() as u32)
+            .expect("write payload length");
+        self.encoder
+            .write_u32::<LittleEndian>(checksum(payload))
+            .expect("write checksum");
+        self.encoder.write_all(payload).expect("write payload");
+    }
+
+    fn finish(self) {
+        self.encoder.finish().expect("finish patch file");
+    }
+}
+
+// Reads a patch written by `PatchWriter` and writes each record's block back
+// into `config.dest`, verifying the magic, version and per-record checksum.
+fn apply_patch(config: &Config, patch_path: &str) -> SyncStats {
+    let mut file =
+        File::open(patch_path).expect(&format!("failed to open patch file ({})", patch_path));
+
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic).expect("read magic");
+    if magic != PATCH_MAGIC {
+        panic!("not a devsync patch file ({})", patch_path);
+    }
+    let version = file.read_u8().expect("read version");
+    if version != PATCH_VERSION {
+        panic!("unsupported patch version ({})", version);
+    }
+    let block_size = file.read_u64::<LittleEndian>().expect("read block_size");
+    let _src_len = file.read_u64::<LittleEndian>().expect("read src_len");
+    let recorded_dest_len = file.read_u64::<LittleEndian>().expect("read dest_len");
+
+    let mut dest_file = OpenOptions::new()
+        .read(true)
+        .write(config.dry_run == false)
+        .open(&config.dest)
+        .expect(&format!("failed to open file ({})", config.dest));
+
+    // The patch's block offsets only make sense against the destination it
+    // was recorded against; applying it to a differently-sized file would
+    // silently write into the wrong place (a gap, or past the real end).
+    let dest_len = dest_file.metadata().expect("stat destination").len();
+    if dest_len != recorded_dest_len {
+        panic!(
+            "patch {} was recorded against a {}-byte destination, but {} is {} bytes",
+            patch_path, recorded_dest_len, config.dest, dest_len
+        );
+    }
+
+    let mut decoder = ZlibDecoder::new(file);
+    let mut stats = SyncStats::default();
+    loop {
+        let block_index = match decoder.read_u64::<LittleEndian>() {
+            Ok(v) => v,
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => panic!("failed to read patch record: {}", e),
+        };
+        let payload_len = decoder.read_u32::<LittleEndian>().expect("read payload length") as usize;
+        let expected_checksum = decoder.read_u32::<LittleEndian>().expect("read checksum");
+        if payload_len as u64 > block_size {
+            panic!(
+                "corrupt patch: payload_len ({}) exceeds block_size ({}) for block {}",
+                payload_len, block_size, block_index
+            );
+        }
+        let mut payload = vec![0u8; payload_len];
+        decoder.read_exact(&mut payload).expect("read payload");
+
+        if checksum(&payload) != expected_checksum {
+            panic!("corrupt patch: checksum mismatch for block {}", block_index);
+        }
+
+        if !config.dry_run {
+            dest_file
+                .seek(SeekFrom::Start(block_index * block_size))
+                .expect("seek");
+            dest_file.write_all(&payload).expect("write");
+        }
+
+        stats.blocks_compared += 1;
+        stats.diff_blocks += 1;
+        stats.diff_bytes += payload_len;
+        stats.total_bytes += payload_len;
+    }
+
+    stats
+}
+
+// Scan `src` block-by-block, writing one hash per block to `manifest_path`
+// so a remote side can later be checked against it without shipping the
+// whole file. With `--dry-run` the source is still scanned (so the reported
+// stats reflect what a real run would cover) but no manifest file is created.
+fn generate_manifest<T>(config: &Config, manifest_path: &str, src: &mut T) -> SyncStats
+where
+    T: Read,
+{
+    let mut out = if config.dry_run {
+        None
+    } else {
+        let mut file = File::create(manifest_path)
+            .expect(&format!("failed to create manifest file ({})", manifest_path));
+        file.write_all(&MANIFEST_MAGIC).expect("write magic");
+        file.write_u8(MANIFEST_VERSION).expect("write version");
+        file.write_u8(config.hash.to_byte()).expect("write hash algo");
+        file.write_u64::<LittleEndian>(config.block_size as u64)
+            .expect("write block_size");
+        Some(file)
+    };
+
+    let mut stats = SyncStats::default();
+    let mut buf: Vec<u8> = vec![0; config.block_size];
+    loop {
+        let read = fill_buf(src, &mut buf);
+        if read == 0 {
+            break;
+        }
+        let hash = config.hash.hash(&buf[..read]);
+        if let Some(ref mut out) = out {
+            out.write_all(&hash).expect("write hash");
+        }
+
+        stats.blocks_compared += 1;
+        stats.total_bytes += read;
+    }
+
+    stats
+}
+
+// Manifest-out has no "destination" in the sync sense (`config.dest` is
+// never touched), so it gets its own summary line instead of reusing
+// `print_summary`'s sync-oriented "written to destination" phrasing.
+fn print_manifest_summary(config: &Config, stats: &SyncStats, manifest_path: &str) {
+    println!(
+        "Scanned {} blocks ({} in total).",
+        stats.blocks_compared,
+        size_to_str(&stats.total_bytes)
+    );
+    if config.dry_run {
+        println!("dry run: manifest was NOT written to {}", manifest_path);
+    } else {
+        println!("manifest written to {}", manifest_path);
+    }
+}
+
+// Verify-and-repair pass: read each destination block, hash it, and compare
+// against the matching expected hash recorded in the manifest. Only blocks
+// whose hash differs are read from `--source` and rewritten, so a
+// mostly-intact destination costs one read per block instead of one
+// read-and-write; outside `--dry-run`, a differing block with no `--source`
+// given panics. With `--dry-run` this simply reports which blocks are
+// corrupt or stale against the manifest.
+fn sync_with_manifest(
+    config: &Config,
+    manifest_path: &str,
+    dest_file: &mut File,
+    live: &LiveStats,
+) -> SyncStats {
+    let mut manifest = File::open(manifest_path)
+        .expect(&format!("failed to open manifest file ({})", manifest_path));
+
+    let mut magic = [0u8; 8];
+    manifest.read_exact(&mut magic).expect("read magic");
+    if magic != MANIFEST_MAGIC {
+        panic!("not a devsync manifest file ({})", manifest_path);
+    }
+    let version = manifest.read_u8().expect("read version");
+    if version != MANIFEST_VERSION {
+        panic!("unsupported manifest version ({})", version);
+    }
+    let algo = HashAlgo::from_byte(manifest.read_u8().expect("read hash algo"));
+    let block_size = manifest.read_u64::<LittleEndian>().expect("read block_size") as usize;
+
+    let mut src_file = config
+        .src
+        .as_ref()
+        .map(|path| File::open(path).expect(&format!("failed to open file ({})", path)));
+
+    let mut stats = SyncStats::default();
+    let mut dest_buf: Vec<u8> = vec![0; block_size];
+    let mut block: usize = 0;
+    loop {
+        let mut expected_hash = [0u8; HASH_LEN];
+        match manifest.read_exact(&mut expected_hash) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => panic!("failed to read manifest record: {}", e),
+        }
+
+        dest_file
+            .seek(SeekFrom::Start((block * block_size) as u64))
+            .expect("seek dest");
+        let dest_read = fill_buf(dest_file, &mut dest_buf);
+        if dest_read == 0 {
+            break;
+        }
+        let dest_hash = algo.hash(&dest_buf[..dest_read]);
+
+        stats.blocks_compared += 1;
+        stats.total_bytes += dest_read;
+        live.blocks_compared.fetch_add(1, Ordering::Relaxed);
+        live.total_bytes.fetch_add(dest_read, Ordering::Relaxed);
+
+        if dest_hash != expected_hash {
+            stats.diff_blocks += 1;
+            stats.diff_bytes += dest_read;
+            live.diff_blocks.fetch_add(1, Ordering::Relaxed);
+            live.diff_bytes.fetch_add(dest_read, Ordering::Relaxed);
+
+            if config.dry_run {
+                println!("block {} differs from manifest", block);
+            } else {
+                let src_file = src_file
+                    .as_mut()
+                    .expect("--manifest-in requires --source to repair mismatched blocks");
+                let mut src_buf: Vec<u8> = vec![0; block_size];
+                src_file
+                    .seek(SeekFrom::Start((block * block_size) as u64))
+                    .expect("seek src");
+                let src_read = fill_buf(src_file, &mut src_buf);
+
+                dest_file
+                    .seek(SeekFrom::Start((block * block_size) as u64))
+                    .expect("seek dest");
+                dest_file
+                    .write_all(&src_buf[..src_read])
+                    .expect("write dest");
+            }
+        }
+
+        block += 1;
+    }
+
+    stats
+}
+
+// Split `nr_blocks` into chunks following the same sizing rule as block-level
+// sync tools: big enough to amortize per-chunk overhead, small enough that
+// `nr_jobs` workers all get a fair share of the file.
+fn build_chunks(nr_blocks: usize, nr_jobs: usize) -> Vec<Chunk> {
+    let chunk_size = cmp::min(4096, cmp::max(128, nr_blocks / (nr_jobs * 64).max(1)));
+    let chunk_size = chunk_size.max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < nr_blocks {
+        let end = cmp::min(start + chunk_size, nr_blocks);
+        chunks.push(Chunk {
+            start_block: start,
+            end_block: end,
+        });
+        start = end;
+    }
+    chunks
+}
+
+// Compare (and, unless dry-run, sync) a single chunk of blocks using its own
+// file handles. Chunks are disjoint, so concurrent writes from other workers
+// never land in this chunk's range.
+fn sync_chunk(config: &Config, src_path: &str, chunk: &Chunk, live: &LiveStats) -> SyncStats {
+    let mut src_file = File::open(src_path).expect(&format!("failed to open file ({})", src_path));
+    let mut dest_file = OpenOptions::new()
+        .read(true)
+        .write(config.dry_run == false)
+        .open(&config.dest)
+        .expect(&format!("failed to open file ({})", config.dest));
+
+    let start_off = (chunk.start_block * config.block_size) as u64;
+    src_file.seek(SeekFrom::Start(start_off)).expect("seek src");
+    dest_file
+        .seek(SeekFrom::Start(start_off))
+        .expect("seek dest");
+
+    let mut stats = SyncStats::default();
+    let mut src_buf: Vec<u8> = vec![0; config.block_size];
+    let mut dest_buf: Vec<u8> = vec![0; config.block_size];
+
+    for block in chunk.start_block..chunk.end_block {
+        let src_read = fill_buf(&mut src_file, &mut src_buf);
+        let dest_read = fill_buf(&mut dest_file, &mut dest_buf);
+        let cmp_size = cmp::min(src_read, dest_read);
+        if cmp_size == 0 {
+            break;
+        }
+
+        let src_slice = &src_buf[..cmp_size];
+        let dest_slice = &dest_buf[..cmp_size];
+        if src_slice != dest_slice {
+            stats.diff_blocks += 1;
+            stats.diff_bytes += cmp_size;
+            live.diff_blocks.fetch_add(1, Ordering::Relaxed);
+            live.diff_bytes.fetch_add(cmp_size, Ordering::Relaxed);
+
+            if !config.dry_run {
+                dest_file
+                    .seek(SeekFrom::Start((block * config.block_size) as u64))
+                    .expect("seek block start");
+                let wc = dest_file.write(src_slice).expect("write");
+                if wc != cmp_size {
+                    panic!("wc != cmp_size");
+                }
+            }
+        }
+
+        stats.blocks_compared += 1;
+        stats.total_bytes += cmp_size;
+        live.blocks_compared.fetch_add(1, Ordering::Relaxed);
+        live.total_bytes.fetch_add(cmp_size, Ordering::Relaxed);
+    }
+
+    stats
+}
+
+// Parallel variant of `sync_files`: split the destination range into chunks,
+// shuffle them so hot regions don't pile up on one worker, and hand them out
+// round-robin across `config.jobs` threads, each with its own file handles.
+fn sync_files_parallel(
+    config: &Config,
+    src_path: &str,
+    nr_blocks: usize,
+    live: Arc<LiveStats>,
+) -> SyncStats {
+    let mut chunks = build_chunks(nr_blocks, config.jobs);
+    chunks.shuffle(&mut thread_rng());
+
+    let (tx, rx) = mpsc::channel();
+    let mut worker_chunks: Vec<Vec<Chunk>> = vec![Vec::new(); config.jobs];
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        worker_chunks[i % config.jobs].push(chunk);
+    }
+
+    let mut handles = Vec::new();
+    for chunks in worker_chunks {
+        let tx = tx.clone();
+        let config = Config {
+            src: config.src.clone(),
+            dest: config.dest.clone(),
+            block_size: config.block_size,
+            buf_size: config.buf_size,
+            dry_run: config.dry_run,
+            jobs: config.jobs,
+            status: config.status,
+            patch: config.patch.clone(),
+            apply: config.apply.clone(),
+            mmap: config.mmap,
+            manifest_out: config.manifest_out.clone(),
+            manifest_in: config.manifest_in.clone(),
+            hash: config.hash,
+            extend: config.extend,
+            truncate: config.truncate,
+        };
+        let src_path = src_path.to_string();
+        let live = live.clone();
+        handles.push(thread::spawn(move || {
+            let mut stats = SyncStats::default();
+            for chunk in &chunks {
+                let chunk_stats = sync_chunk(&config, &src_path, chunk, &live);
+                stats.blocks_compared += chunk_stats.blocks_compared;
+                stats.diff_blocks += chunk_stats.diff_blocks;
+                stats.diff_bytes += chunk_stats.diff_bytes;
+                stats.total_bytes += chunk_stats.total_bytes;
+            }
+            tx.send(stats).expect("send stats");
+        }));
+    }
+    drop(tx);
+
+    let mut total = SyncStats::default();
+    for stats in rx {
+        total.blocks_compared += stats.blocks_compared;
+        total.diff_blocks += stats.diff_blocks;
+        total.diff_bytes += stats.diff_bytes;
+        total.total_bytes += stats.total_bytes;
+    }
+    for handle in handles {
+        handle.join().expect("join worker");
+    }
+
+    total
 }
 
 fn main() {
     let config = parse_opts();
 
+    // --patch records a diff against the default buffered path only; the
+    // other top-level modes never reach the code that would build one, so
+    // reject the combination up front instead of silently dropping it.
+    if config.patch.is_some()
+        && (config.apply.is_some()
+            || config.manifest_out.is_some()
+            || config.manifest_in.is_some()
+            || config.mmap
+            || config.jobs > 1)
+    {
+        eprintln!("--patch is not supported together with --apply, --manifest-out, --manifest-in, --mmap or --jobs.");
+        process::exit(1);
+    }
+
+    if let Some(patch_path) = config.apply.clone() {
+        let stats = apply_patch(&config, &patch_path);
+        print_summary(&config, &stats);
+        return;
+    }
+
+    if let Some(manifest_path) = config.manifest_out.clone() {
+        let stats = if let Some(val) = &config.src {
+            let mut src_file = File::open(&val).expect(&format!("failed to open file ({})", val));
+            generate_manifest(&config, &manifest_path, &mut src_file)
+        } else {
+            let stdin = io::stdin();
+            let mut src_file = stdin.lock();
+            generate_manifest(&config, &manifest_path, &mut src_file)
+        };
+        print_manifest_summary(&config, &stats, &manifest_path);
+        return;
+    }
+
+    install_usr1_handler();
+    let live = Arc::new(LiveStats::new());
+    let stop = Arc::new(AtomicBool::new(false));
+    let status_thread = {
+        let status = config.status;
+        let live = live.clone();
+        let stop = stop.clone();
+        thread::spawn(move || run_status_thread(status, live, stop))
+    };
+
+    if let Some(manifest_path) = config.manifest_in.clone() {
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .write(config.dry_run == false)
+            .open(&config.dest)
+            .expect(&format!("failed to open file ({})", config.dest));
+        let mut stats = sync_with_manifest(&config, &manifest_path, &mut dest_file, &live);
+        let (extended, truncated) = finalize_length_mismatch(&config, &mut dest_file);
+        stats.extended_bytes = extended;
+        stats.truncated_bytes = truncated;
+        stop.store(true, Ordering::SeqCst);
+        status_thread.join().expect("join status thread");
+        print_summary(&config, &stats);
+        return;
+    }
+
+    if config.jobs > 1 {
+        if let Some(src_path) = &config.src {
+            let src_meta = std::fs::metadata(src_path).expect("stat source");
+            let dest_meta = std::fs::metadata(&config.dest).expect("stat destination");
+            let common_len = cmp::min(src_meta.len(), dest_meta.len()) as usize;
+            // Ceiling-divide so a trailing partial block (file length not a
+            // multiple of block_size) is still assigned to a chunk and compared.
+            let nr_blocks = common_len.div_ceil(config.block_size);
+            let mut stats = sync_files_parallel(&config, src_path, nr_blocks, live);
+            let mut dest_file = OpenOptions::new()
+                .read(true)
+                .write(config.dry_run == false)
+                .open(&config.dest)
+                .expect(&format!("failed to open file ({})", config.dest));
+            let (extended, truncated) = finalize_length_mismatch(&config, &mut dest_file);
+            stats.extended_bytes = extended;
+            stats.truncated_bytes = truncated;
+            stop.store(true, Ordering::SeqCst);
+            status_thread.join().expect("join status thread");
+            print_summary(&config, &stats);
+            return;
+        } else {
+            eprintln!("--jobs requires a seekable --source; falling back to a single thread.");
+        }
+    }
+
     let mut dest_file = OpenOptions::new()
         .read(true)
         // Open with write access if not a 'dry run'
@@ -226,14 +1241,489 @@ fn main() {
         .open(&config.dest)
         .expect(&format!("failed to open file ({})", config.dest));
 
-    if let Some(val) = &config.src {
+    if config.mmap {
+        if let Some(src_path) = &config.src {
+            let mut stats = sync_files_mmap(&config, src_path, &mut dest_file, &live);
+            let (extended, truncated) = finalize_length_mismatch(&config, &mut dest_file);
+            stats.extended_bytes = extended;
+            stats.truncated_bytes = truncated;
+            stop.store(true, Ordering::SeqCst);
+            status_thread.join().expect("join status thread");
+            print_summary(&config, &stats);
+            return;
+        } else {
+            eprintln!("--mmap requires a seekable --source; falling back to the buffered path.");
+        }
+    }
+
+    let mut patch_writer = config.patch.as_ref().map(|patch_path| {
+        let src_len = config
+            .src
+            .as_ref()
+            .map(|p| std::fs::metadata(p).expect("stat source").len())
+            .unwrap_or(0);
+        let dest_len = dest_file.metadata().expect("stat destination").len();
+        PatchWriter::create(patch_path, config.block_size, src_len, dest_len)
+    });
+
+    let stats = if let Some(val) = &config.src {
         // Read from input file
         let mut src_file = File::open(&val).expect(&format!("failed to open file ({})", val));
-        sync_files(&config, &mut src_file, &mut dest_file);
+        sync_files(&config, &mut src_file, &mut dest_file, &live, patch_writer.as_mut())
     } else {
         // Read from stdin
         let stdin = io::stdin();
         let mut src_file = stdin.lock();
-        sync_files(&config, &mut src_file, &mut dest_file);
+        sync_files(&config, &mut src_file, &mut dest_file, &live, patch_writer.as_mut())
+    };
+    if let Some(writer) = patch_writer {
+        writer.finish();
+    }
+    stop.store(true, Ordering::SeqCst);
+    status_thread.join().expect("join status thread");
+    print_summary(&config, &stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config_with_src(dest: &str, src: Option<&str>) -> Config {
+        Config {
+            src: src.map(|s| s.to_string()),
+            dest: dest.to_string(),
+            block_size: 64,
+            buf_size: 64,
+            dry_run: false,
+            jobs: 1,
+            status: StatusLevel::None,
+            patch: None,
+            apply: None,
+            mmap: false,
+            manifest_out: None,
+            manifest_in: None,
+            hash: HashAlgo::Blake3,
+            extend: false,
+            truncate: false,
+        }
+    }
+
+    fn test_config(dest: &str) -> Config {
+        Config {
+            src: None,
+            dest: dest.to_string(),
+            block_size: 64,
+            buf_size: 64,
+            dry_run: false,
+            jobs: 1,
+            status: StatusLevel::None,
+            patch: None,
+            apply: None,
+            mmap: false,
+            manifest_out: None,
+            manifest_in: None,
+            hash: HashAlgo::Blake3,
+            extend: false,
+            truncate: false,
+        }
+    }
+
+    fn unique_path(name: &str) -> String {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir()
+            .join(format!("devsync_test_{}_{}_{}", process::id(), n, name))
+            .to_str()
+            .expect("path")
+            .to_string()
+    }
+
+    fn write_patch_header(file: &mut File, block_size: u64, dest_len: u64) {
+        file.write_all(&PATCH_MAGIC).expect("write magic");
+        file.write_u8(PATCH_VERSION).expect("write version");
+        file.write_u64::<LittleEndian>(block_size)
+            .expect("write block_size");
+        file.write_u64::<LittleEndian>(0).expect("write src_len");
+        file.write_u64::<LittleEndian>(dest_len)
+            .expect("write dest_len");
+    }
+
+    #[test]
+    fn patch_round_trip_writes_matching_block() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+        let patch_path = unique_path("patch");
+
+        let mut writer = PatchWriter::create(&patch_path, 64, 64, 64);
+        writer.write_record(0, &[7u8; 64]);
+        writer.finish();
+
+        let stats = apply_patch(&test_config(&dest_path), &patch_path);
+
+        assert_eq!(stats.blocks_compared, 1);
+        assert_eq!(stats.diff_blocks, 1);
+        assert_eq!(stats.diff_bytes, 64);
+        assert_eq!(std::fs::read(&dest_path).expect("read dest"), vec![7u8; 64]);
+
+        std::fs::remove_file(&dest_path).ok();
+        std::fs::remove_file(&patch_path).ok();
+    }
+
+    #[test]
+    fn patch_with_no_records_leaves_destination_untouched() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![1u8; 64]).expect("write dest");
+        let patch_path = unique_path("patch");
+
+        let writer = PatchWriter::create(&patch_path, 64, 64, 64);
+        writer.finish();
+
+        let stats = apply_patch(&test_config(&dest_path), &patch_path);
+
+        assert_eq!(stats.blocks_compared, 0);
+        assert_eq!(std::fs::read(&dest_path).expect("read dest"), vec![1u8; 64]);
+
+        std::fs::remove_file(&dest_path).ok();
+        std::fs::remove_file(&patch_path).ok();
+    }
+
+    #[test]
+    #[should_panic(expected = "not a devsync patch file")]
+    fn patch_with_wrong_magic_is_rejected() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+        let patch_path = unique_path("patch");
+        std::fs::write(&patch_path, vec![0u8; 32]).expect("write bogus patch");
+
+        apply_patch(&test_config(&dest_path), &patch_path);
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported patch version")]
+    fn patch_with_wrong_version_is_rejected() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+        let patch_path = unique_path("patch");
+
+        let mut file = File::create(&patch_path).expect("create patch");
+        file.write_all(&PATCH_MAGIC).expect("write magic");
+        file.write_u8(99).expect("write version");
+        drop(file);
+
+        apply_patch(&test_config(&dest_path), &patch_path);
+    }
+
+    #[test]
+    #[should_panic(expected = "checksum mismatch")]
+    fn patch_with_corrupt_checksum_is_rejected() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+        let patch_path = unique_path("patch");
+
+        let mut file = File::create(&patch_path).expect("create patch");
+        write_patch_header(&mut file, 64, 64);
+        let mut encoder = ZlibEncoder::new(file, Compression::default());
+        let payload = vec![7u8; 64];
+        encoder.write_u64::<LittleEndian>(0).expect("write block index");
+        encoder
+            .write_u32::<LittleEndian>(payload.len() as u32)
+            .expect("write payload length");
+        encoder
+            .write_u32::<LittleEndian>(checksum(&payload).wrapping_add(1))
+            .expect("write checksum");
+        encoder.write_all(&payload).expect("write payload");
+        encoder.finish().expect("finish patch");
+
+        apply_patch(&test_config(&dest_path), &patch_path);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds block_size")]
+    fn patch_with_oversized_payload_is_rejected() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![0u8; 128]).expect("write dest");
+        let patch_path = unique_path("patch");
+
+        let mut file = File::create(&patch_path).expect("create patch");
+        write_patch_header(&mut file, 64, 128);
+        let mut encoder = ZlibEncoder::new(file, Compression::default());
+        let payload = vec![7u8; 100];
+        encoder.write_u64::<LittleEndian>(0).expect("write block index");
+        encoder
+            .write_u32::<LittleEndian>(payload.len() as u32)
+            .expect("write payload length");
+        encoder
+            .write_u32::<LittleEndian>(checksum(&payload))
+            .expect("write checksum");
+        encoder.write_all(&payload).expect("write payload");
+        encoder.finish().expect("finish patch");
+
+        apply_patch(&test_config(&dest_path), &patch_path);
+    }
+
+    #[test]
+    #[should_panic(expected = "was recorded against a 64-byte destination")]
+    fn patch_with_mismatched_dest_len_is_rejected() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![0u8; 128]).expect("write dest");
+        let patch_path = unique_path("patch");
+
+        let mut writer = PatchWriter::create(&patch_path, 64, 64, 64);
+        writer.write_record(0, &[7u8; 64]);
+        writer.finish();
+
+        apply_patch(&test_config(&dest_path), &patch_path);
+    }
+
+    #[test]
+    fn manifest_round_trip_repairs_differing_block() {
+        let src_path = unique_path("src");
+        let dest_path = unique_path("dest");
+        let manifest_path = unique_path("manifest");
+        std::fs::write(&src_path, vec![7u8; 64]).expect("write src");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+
+        let config = test_config_with_src(&dest_path, Some(&src_path));
+        let mut src_file = File::open(&src_path).expect("open src");
+        generate_manifest(&config, &manifest_path, &mut src_file);
+
+        let live = LiveStats::new();
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        let stats = sync_with_manifest(&config, &manifest_path, &mut dest_file, &live);
+
+        assert_eq!(stats.blocks_compared, 1);
+        assert_eq!(stats.diff_blocks, 1);
+        assert_eq!(std::fs::read(&dest_path).expect("read dest"), vec![7u8; 64]);
+
+        std::fs::remove_file(&src_path).ok();
+        std::fs::remove_file(&dest_path).ok();
+        std::fs::remove_file(&manifest_path).ok();
+    }
+
+    #[test]
+    fn manifest_with_matching_block_reports_no_diff() {
+        let src_path = unique_path("src");
+        let dest_path = unique_path("dest");
+        let manifest_path = unique_path("manifest");
+        std::fs::write(&src_path, vec![7u8; 64]).expect("write src");
+        std::fs::write(&dest_path, vec![7u8; 64]).expect("write dest");
+
+        let config = test_config_with_src(&dest_path, Some(&src_path));
+        let mut src_file = File::open(&src_path).expect("open src");
+        generate_manifest(&config, &manifest_path, &mut src_file);
+
+        let live = LiveStats::new();
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        let stats = sync_with_manifest(&config, &manifest_path, &mut dest_file, &live);
+
+        assert_eq!(stats.blocks_compared, 1);
+        assert_eq!(stats.diff_blocks, 0);
+
+        std::fs::remove_file(&src_path).ok();
+        std::fs::remove_file(&dest_path).ok();
+        std::fs::remove_file(&manifest_path).ok();
+    }
+
+    #[test]
+    fn manifest_of_empty_source_has_no_blocks() {
+        let src_path = unique_path("src");
+        let dest_path = unique_path("dest");
+        let manifest_path = unique_path("manifest");
+        std::fs::write(&src_path, Vec::<u8>::new()).expect("write src");
+        std::fs::write(&dest_path, Vec::<u8>::new()).expect("write dest");
+
+        let config = test_config_with_src(&dest_path, Some(&src_path));
+        let mut src_file = File::open(&src_path).expect("open src");
+        let gen_stats = generate_manifest(&config, &manifest_path, &mut src_file);
+        assert_eq!(gen_stats.blocks_compared, 0);
+
+        let live = LiveStats::new();
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        let stats = sync_with_manifest(&config, &manifest_path, &mut dest_file, &live);
+        assert_eq!(stats.blocks_compared, 0);
+
+        std::fs::remove_file(&src_path).ok();
+        std::fs::remove_file(&dest_path).ok();
+        std::fs::remove_file(&manifest_path).ok();
+    }
+
+    #[test]
+    #[should_panic(expected = "not a devsync manifest file")]
+    fn manifest_with_wrong_magic_is_rejected() {
+        let dest_path = unique_path("dest");
+        let manifest_path = unique_path("manifest");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+        std::fs::write(&manifest_path, vec![0u8; 32]).expect("write bogus manifest");
+
+        let config = test_config(&dest_path);
+        let live = LiveStats::new();
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        sync_with_manifest(&config, &manifest_path, &mut dest_file, &live);
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported manifest version")]
+    fn manifest_with_wrong_version_is_rejected() {
+        let dest_path = unique_path("dest");
+        let manifest_path = unique_path("manifest");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+
+        let mut file = File::create(&manifest_path).expect("create manifest");
+        file.write_all(&MANIFEST_MAGIC).expect("write magic");
+        file.write_u8(99).expect("write version");
+        drop(file);
+
+        let config = test_config(&dest_path);
+        let live = LiveStats::new();
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        sync_with_manifest(&config, &manifest_path, &mut dest_file, &live);
+    }
+
+    #[test]
+    fn build_chunks_covers_every_block_disjointly_and_in_order() {
+        // A block count that isn't a multiple of the chunk size, so the
+        // last chunk is a short one.
+        let chunks = build_chunks(200, 4);
+        assert!(!chunks.is_empty());
+        assert_eq!(chunks[0].start_block, 0);
+        assert_eq!(chunks.last().expect("last chunk").end_block, 200);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end_block, pair[1].start_block);
+            assert!(pair[0].start_block < pair[0].end_block);
+        }
+    }
+
+    #[test]
+    fn build_chunks_of_zero_blocks_is_empty() {
+        assert!(build_chunks(0, 4).is_empty());
+    }
+
+    #[test]
+    fn sync_files_mmap_repairs_differing_block_and_trailing_partial_block() {
+        let src_path = unique_path("src");
+        let dest_path = unique_path("dest");
+        // 64 (one full block) + 10 (trailing partial block) bytes.
+        let mut src_data = vec![7u8; 64];
+        src_data.extend(vec![9u8; 10]);
+        let mut dest_data = vec![0u8; 64];
+        dest_data.extend(vec![0u8; 10]);
+        std::fs::write(&src_path, &src_data).expect("write src");
+        std::fs::write(&dest_path, &dest_data).expect("write dest");
+
+        let config = test_config_with_src(&dest_path, Some(&src_path));
+        let live = LiveStats::new();
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        let stats = sync_files_mmap(&config, &src_path, &mut dest_file, &live);
+
+        assert_eq!(stats.blocks_compared, 2);
+        assert_eq!(stats.diff_blocks, 2);
+        drop(dest_file);
+        assert_eq!(std::fs::read(&dest_path).expect("read dest"), src_data);
+
+        std::fs::remove_file(&src_path).ok();
+        std::fs::remove_file(&dest_path).ok();
+    }
+
+    #[test]
+    fn sync_files_mmap_dry_run_leaves_destination_untouched() {
+        let src_path = unique_path("src");
+        let dest_path = unique_path("dest");
+        std::fs::write(&src_path, vec![7u8; 64]).expect("write src");
+        std::fs::write(&dest_path, vec![0u8; 64]).expect("write dest");
+
+        let mut config = test_config_with_src(&dest_path, Some(&src_path));
+        config.dry_run = true;
+        let live = LiveStats::new();
+        let mut dest_file = OpenOptions::new()
+            .read(true)
+            .open(&dest_path)
+            .expect("open dest");
+        let stats = sync_files_mmap(&config, &src_path, &mut dest_file, &live);
+
+        assert_eq!(stats.diff_blocks, 1);
+        assert_eq!(std::fs::read(&dest_path).expect("read dest"), vec![0u8; 64]);
+
+        std::fs::remove_file(&src_path).ok();
+        std::fs::remove_file(&dest_path).ok();
+    }
+
+    #[test]
+    fn extend_destination_appends_leftover_and_rest_of_source() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![1u8; 64]).expect("write dest");
+
+        let config = test_config(&dest_path);
+        let mut dest_file = OpenOptions::new()
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        let mut rest = &[9u8; 32][..];
+        let appended = extend_destination(&config, &mut dest_file, 64, &[8u8; 16], &mut rest);
+
+        assert_eq!(appended, 48);
+        let mut expected = vec![1u8; 64];
+        expected.extend(vec![8u8; 16]);
+        expected.extend(vec![9u8; 32]);
+        assert_eq!(std::fs::read(&dest_path).expect("read dest"), expected);
+
+        std::fs::remove_file(&dest_path).ok();
+    }
+
+    #[test]
+    fn truncate_destination_drops_trailing_bytes() {
+        let dest_path = unique_path("dest");
+        std::fs::write(&dest_path, vec![1u8; 96]).expect("write dest");
+
+        let mut dest_file = OpenOptions::new()
+            .write(true)
+            .open(&dest_path)
+            .expect("open dest");
+        let dropped = truncate_destination(&mut dest_file, 64);
+
+        assert_eq!(dropped, 32);
+        assert_eq!(std::fs::read(&dest_path).expect("read dest"), vec![1u8; 64]);
+
+        std::fs::remove_file(&dest_path).ok();
+    }
+
+    #[test]
+    fn live_stats_snapshot_reflects_accumulated_counters() {
+        // Exercises the same atomic counters the SIGUSR1 status dump reads
+        // from, without relying on signal delivery or thread timing.
+        let live = LiveStats::new();
+        live.blocks_compared.fetch_add(3, Ordering::Relaxed);
+        live.diff_blocks.fetch_add(1, Ordering::Relaxed);
+        live.diff_bytes.fetch_add(64, Ordering::Relaxed);
+        live.total_bytes.fetch_add(192, Ordering::Relaxed);
+
+        let stats = live.snapshot();
+        assert_eq!(stats.blocks_compared, 3);
+        assert_eq!(stats.diff_blocks, 1);
+        assert_eq!(stats.diff_bytes, 64);
+        assert_eq!(stats.total_bytes, 192);
     }
 }